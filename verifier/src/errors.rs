@@ -0,0 +1,79 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use core::fmt;
+use fri::VerifierError as FriVerifierError;
+use utils::DeserializationError;
+
+// VERIFIER ERROR
+// ================================================================================================
+/// Defines errors which can occur during STARK proof verification.
+#[derive(Debug, PartialEq, Eq)]
+pub enum VerifierError {
+    /// This error occurs when a proof was deserialized incorrectly, or was generated for a
+    /// different computation than the one provided to the verifier.
+    ProofDeserializationError(DeserializationError),
+    /// This error occurs when a proof was generated using an unsupported field extension degree.
+    UnsupportedFieldExtension(usize),
+    /// This error occurs when queried trace evaluations read from a proof do not match the
+    /// commitment made by the prover, or when the number of queries read from a proof does not
+    /// match the number of query positions the verifier has drawn from the public coin.
+    TraceQueryDoesNotMatchCommitment,
+    /// This error occurs when queried constraint evaluations read from a proof do not match the
+    /// commitment made by the prover, or when their number does not match the number of query
+    /// positions they are read for.
+    ConstraintQueryDoesNotMatchCommitment,
+    /// This error occurs when the public coin fails to generate a valid set of random values,
+    /// usually either because the proof was malformed, or because an unexpected number of draws
+    /// was requested.
+    RandomCoinError,
+    /// This error occurs when constraints evaluated over out-of-domain frame do not match
+    /// evaluations of the constraint composition polynomial sent by the prover.
+    InconsistentOodConstraintEvaluations,
+    /// This error occurs when the proof-of-work nonce sent by the prover does not result in a
+    /// hash with the required number of leading zeros.
+    QuerySeedProofOfWorkVerificationFailed,
+    /// This error occurs when the low-degree proof generated by the prover is invalid.
+    FriVerificationFailed(FriVerifierError),
+    /// This error occurs when a proof was generated with [ProofOptions](air::ProofOptions) which
+    /// do not meet the minimum security requirements specified by the verifier's
+    /// [AcceptableOptions](crate::AcceptableOptions).
+    InsufficientProofOptions,
+}
+
+impl fmt::Display for VerifierError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ProofDeserializationError(err) => {
+                write!(f, "proof deserialization failed: {err}")
+            }
+            Self::UnsupportedFieldExtension(degree) => {
+                write!(f, "field extension of degree {degree} is not supported")
+            }
+            Self::TraceQueryDoesNotMatchCommitment => {
+                write!(f, "trace query did not match the commitment")
+            }
+            Self::ConstraintQueryDoesNotMatchCommitment => {
+                write!(f, "constraint query did not match the commitment")
+            }
+            Self::RandomCoinError => {
+                write!(f, "public coin failed to generate a valid random value")
+            }
+            Self::InconsistentOodConstraintEvaluations => write!(
+                f,
+                "out-of-domain constraint evaluations are inconsistent with the evaluations \
+                derived from the out-of-domain trace frame"
+            ),
+            Self::QuerySeedProofOfWorkVerificationFailed => {
+                write!(f, "proof-of-work verification failed")
+            }
+            Self::FriVerificationFailed(err) => write!(f, "FRI verification failed: {err}"),
+            Self::InsufficientProofOptions => write!(
+                f,
+                "proof options do not meet the minimum security requirements of the verifier"
+            ),
+        }
+    }
+}
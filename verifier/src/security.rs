@@ -0,0 +1,277 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use air::{Air, FieldExtension, ProofOptions};
+use crypto::ElementHasher;
+use math::StarkField;
+use utils::collections::Vec;
+
+use crate::errors::VerifierError;
+
+// ACCEPTABLE OPTIONS
+// ================================================================================================
+/// Describes a set of proof parameters acceptable to a verifier.
+///
+/// A prover is free to choose [ProofOptions] which trade proof size for security (e.g. by
+/// lowering `num_queries`, `blowup_factor`, or `grinding_factor`). Without an explicit floor, a
+/// dishonest prover could use weak parameters and still produce a proof which `verify()` would
+/// happily accept. [AcceptableOptions] lets a verifier pin down what it is actually willing to
+/// trust, either by listing the exact [ProofOptions] it will accept, or by requiring a minimum
+/// number of bits of conjectured or proven security, computed from the options embedded in the
+/// proof itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AcceptableOptions {
+    /// A proof is accepted only if its options are equal to one of the entries in this list.
+    OptionSet(Vec<ProofOptions>),
+    /// A proof is accepted only if its options imply at least this many bits of conjectured
+    /// security (see [compute_conjectured_security]).
+    MinConjecturedSecurity(u32),
+    /// A proof is accepted only if its options imply at least this many bits of proven security
+    /// (see [compute_proven_security]).
+    MinProvenSecurity(u32),
+}
+
+impl AcceptableOptions {
+    /// Returns true if the provided `options` satisfy these acceptable options, given the
+    /// additional context (field sizes and domain size) needed to estimate security.
+    pub(crate) fn is_satisfied_by(&self, options: &ProofOptions, ctx: &SecurityContext) -> bool {
+        match self {
+            Self::OptionSet(set) => set.iter().any(|o| o == options),
+            Self::MinConjecturedSecurity(min_bits) => {
+                compute_conjectured_security(options, ctx) >= *min_bits
+            }
+            Self::MinProvenSecurity(min_bits) => {
+                compute_proven_security(options, ctx) >= *min_bits
+            }
+        }
+    }
+}
+
+// SECURITY CONTEXT
+// ================================================================================================
+/// Holds the pieces of context (outside of [ProofOptions] itself) needed to turn a set of proof
+/// options into a concrete bit-security estimate: the size of the LDE domain the proof was
+/// generated against, and the bit-widths of the base and extension fields.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SecurityContext {
+    /// log2 of the size of the LDE domain.
+    pub lde_domain_size_bits: u32,
+    /// Number of bits in the base field modulus.
+    pub base_field_bits: u32,
+    /// Degree of the extension field used for the randomized portion of the protocol.
+    pub extension_degree: u32,
+    /// Collision resistance, in bits, of the hash function used to build Merkle commitments
+    /// (i.e. half of its output width).
+    pub collision_resistance_bits: u32,
+}
+
+impl SecurityContext {
+    fn extension_field_bits(&self) -> u32 {
+        self.base_field_bits * self.extension_degree
+    }
+}
+
+/// Builds a [SecurityContext] for `air`/`HashFn` and rejects if the resulting estimate does not
+/// satisfy `acceptable_options`.
+///
+/// This is the single place that turns an [Air] instance and a hash function into the context
+/// needed to evaluate [AcceptableOptions], so every entry point that can produce a verification
+/// result - [crate::verify()], [crate::verify_async()], and the staged [crate::Verifier] used
+/// directly by recursive/aggregated setups - enforces the exact same security floor rather than
+/// each keeping its own copy of this check.
+pub(crate) fn enforce_acceptable_options<A, HashFn>(
+    air: &A,
+    acceptable_options: &AcceptableOptions,
+) -> Result<(), VerifierError>
+where
+    A: Air,
+    HashFn: ElementHasher<BaseField = A::BaseField>,
+{
+    let extension_degree = match air.options().field_extension() {
+        FieldExtension::None => 1,
+        FieldExtension::Quadratic => 2,
+        FieldExtension::Cubic => 3,
+    };
+    let ctx = SecurityContext {
+        lde_domain_size_bits: air.lde_domain_size().ilog2(),
+        base_field_bits: A::BaseField::MODULUS_BITS,
+        extension_degree,
+        collision_resistance_bits: HashFn::COLLISION_RESISTANCE,
+    };
+    if acceptable_options.is_satisfied_by(air.options(), &ctx) {
+        Ok(())
+    } else {
+        Err(VerifierError::InsufficientProofOptions)
+    }
+}
+
+// SECURITY ESTIMATION
+// ================================================================================================
+/// Estimates the conjectured bits of security provided by `options`, i.e. the security level
+/// assuming the hardest known attacks against the FRI protocol (rather than a proven bound).
+///
+/// This is `min(collision_bits, field_bits, query_bits)` where:
+/// - `collision_bits` is the collision resistance, in bits, of the hash function used for Merkle
+///   commitments.
+/// - `field_bits = extension_field_bits - log2(lde_domain_size)` accounts for the fact that a
+///   cheating prover only needs to find a collision within the LDE domain, not across the whole
+///   field.
+/// - `query_bits = num_queries * log2(blowup_factor) + grinding_factor` is the probability that
+///   all drawn query positions land on a codeword which agrees with a low-degree polynomial it
+///   should not.
+pub(crate) fn compute_conjectured_security(options: &ProofOptions, ctx: &SecurityContext) -> u32 {
+    let collision_bits = ctx.collision_resistance_bits;
+    let field_bits = ctx
+        .extension_field_bits()
+        .saturating_sub(ctx.lde_domain_size_bits);
+    let query_bits = options.num_queries() as u32 * log2_floor(options.blowup_factor() as u64)
+        + options.grinding_factor() as u32;
+
+    collision_bits.min(field_bits).min(query_bits)
+}
+
+/// Estimates the proven bits of security provided by `options`, i.e. the security level backed
+/// by the FRI list-decoding bound rather than conjectured hardness.
+///
+/// This mirrors [compute_conjectured_security], but replaces `query_bits` with the (weaker) FRI
+/// list-decoding bound `num_queries * log2(blowup_factor) / 2`, further reduced by a small
+/// additive term accounting for the number of FRI folding rounds, and clamped so that it can
+/// never exceed the collision resistance of the hash function.
+pub(crate) fn compute_proven_security(options: &ProofOptions, ctx: &SecurityContext) -> u32 {
+    let collision_bits = ctx.collision_resistance_bits;
+    let field_bits = ctx
+        .extension_field_bits()
+        .saturating_sub(ctx.lde_domain_size_bits);
+
+    let list_decoding_bits =
+        (options.num_queries() as u32 * log2_floor(options.blowup_factor() as u64)) / 2;
+    let num_fri_layers = num_fri_layers(
+        1usize << ctx.lde_domain_size_bits,
+        options.to_fri_options().folding_factor(),
+        options.to_fri_options().remainder_max_degree(),
+    );
+    let query_bits = list_decoding_bits
+        .saturating_sub(num_fri_layers as u32)
+        .min(collision_bits);
+
+    collision_bits.min(field_bits).min(query_bits)
+}
+
+/// Returns the number of FRI folding rounds needed to reduce a domain of size `lde_domain_size`
+/// down to a remainder of at most `remainder_max_degree + 1` evaluations, folding by
+/// `folding_factor` at each round.
+fn num_fri_layers(lde_domain_size: usize, folding_factor: usize, remainder_max_degree: usize) -> usize {
+    let mut domain_size = lde_domain_size;
+    let mut num_layers = 0;
+    while domain_size > remainder_max_degree + 1 {
+        domain_size /= folding_factor;
+        num_layers += 1;
+    }
+    num_layers
+}
+
+/// Returns `floor(log2(value))`; `value` is expected to be a power of two, as is the case for
+/// the blowup factor of a STARK proof.
+fn log2_floor(value: u64) -> u32 {
+    63 - value.leading_zeros()
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::hashers::Blake3_256;
+    use math::fields::f128::BaseElement;
+
+    fn options(num_queries: usize, blowup_factor: usize, grinding_factor: u32) -> ProofOptions {
+        ProofOptions::new(
+            num_queries,
+            blowup_factor,
+            grinding_factor,
+            air::HashFunction::Blake3_256,
+            FieldExtension::None,
+            8,
+            31,
+        )
+    }
+
+    fn ctx(lde_domain_size_bits: u32, extension_degree: u32) -> SecurityContext {
+        SecurityContext {
+            lde_domain_size_bits,
+            base_field_bits: BaseElement::MODULUS_BITS,
+            extension_degree,
+            collision_resistance_bits: Blake3_256::<BaseElement>::COLLISION_RESISTANCE,
+        }
+    }
+
+    #[test]
+    fn conjectured_security_is_bounded_by_collision_resistance() {
+        // a huge number of queries and a huge blowup factor should still never report more bits
+        // of security than the hash function's collision resistance allows.
+        let options = options(1000, 1 << 16, 32);
+        let ctx = ctx(20, 2);
+        assert_eq!(
+            compute_conjectured_security(&options, &ctx),
+            ctx.collision_resistance_bits
+        );
+    }
+
+    #[test]
+    fn conjectured_security_drops_with_fewer_queries() {
+        let weak = options(1, 4, 0);
+        let strong = options(64, 4, 0);
+        let ctx = ctx(20, 2);
+        assert!(compute_conjectured_security(&weak, &ctx) < compute_conjectured_security(&strong, &ctx));
+    }
+
+    #[test]
+    fn conjectured_security_matches_query_bits_for_one_query_no_grinding() {
+        // query_bits = num_queries * log2(blowup_factor) + grinding_factor = 1 * 1 + 0 = 1, which
+        // is far below collision_bits and field_bits here, so it is what the min() picks.
+        let weak = options(1, 2, 0);
+        let ctx = ctx(20, 2);
+        assert_eq!(compute_conjectured_security(&weak, &ctx), 1);
+    }
+
+    #[test]
+    fn proven_security_never_exceeds_conjectured_security() {
+        // the proven bound is never stronger than the conjectured one, since it accounts for the
+        // FRI list-decoding bound rather than idealized query soundness.
+        let options = options(32, 8, 16);
+        let ctx = ctx(20, 2);
+        assert!(compute_proven_security(&options, &ctx) <= compute_conjectured_security(&options, &ctx));
+    }
+
+    #[test]
+    fn proven_security_is_bounded_by_collision_resistance() {
+        let options = options(1000, 1 << 16, 32);
+        let ctx = ctx(20, 2);
+        assert!(compute_proven_security(&options, &ctx) <= ctx.collision_resistance_bits);
+    }
+
+    #[test]
+    fn acceptable_options_enforces_min_conjectured_security() {
+        let weak = options(1, 2, 0);
+        let ctx = ctx(20, 2);
+        let floor = AcceptableOptions::MinConjecturedSecurity(80);
+        assert!(!floor.is_satisfied_by(&weak, &ctx));
+
+        let strong = options(64, 1 << 8, 16);
+        assert!(floor.is_satisfied_by(&strong, &ctx));
+    }
+
+    #[test]
+    fn acceptable_options_option_set_rejects_unlisted_options() {
+        let allowed = options(32, 8, 16);
+        let other = options(16, 8, 16);
+        let ctx = ctx(20, 2);
+        let mut set = Vec::new();
+        set.push(allowed.clone());
+        let set = AcceptableOptions::OptionSet(set);
+        assert!(set.is_satisfied_by(&allowed, &ctx));
+        assert!(!set.is_satisfied_by(&other, &ctx));
+    }
+}
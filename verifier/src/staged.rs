@@ -0,0 +1,437 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use air::{
+    Air, AuxTraceRandElements, ConstraintCompositionCoefficients, DeepCompositionCoefficients,
+    EvaluationFrame,
+};
+use crypto::{ElementHasher, RandomCoin};
+use fri::FriVerifier;
+use math::FieldElement;
+use utils::collections::Vec;
+
+use crate::{
+    channel::VerifierChannel,
+    composer::DeepComposer,
+    errors::VerifierError,
+    evaluator::evaluate_constraints,
+    security::{self, AcceptableOptions},
+    transcript::{
+        domain_tag, CONSTRAINT_COMMITMENT_LABEL, FRI_LAYER_LABEL, OOD_CONSTRAINT_EVALUATIONS_LABEL,
+        OOD_TRACE_FRAME_LABEL, POW_NONCE_LABEL, TRACE_COMMITMENT_LABEL,
+    },
+};
+
+// PHASE BODIES
+// ================================================================================================
+// Each phase below that reads from `channel` has a synchronous and (behind the `async` feature)
+// an async entry point. The two only ever differ in how that one read is performed - blocking or
+// `.await`ed - so each phase's logic is written once, here, as a macro parameterized over the
+// already-read value(s); the `impl` blocks further down just supply the read expression. This is
+// this crate's take on the "maybe-async" dual-codegen pattern without pulling in a proc-macro
+// dependency: a future fix to, say, the reseed order in `check_ood_consistency` only has to be
+// made in `check_ood_consistency_shared!` to apply to both the sync and async driver.
+
+macro_rules! commit_trace_shared {
+    ($self:expr, $trace_commitments:expr) => {{
+        let trace_commitments = $trace_commitments;
+
+        // reseed the coin with the commitment to the main trace segment, preceded by a label
+        // identifying this as a trace-commitment reseed
+        $self.public_coin.reseed(domain_tag::<H>(TRACE_COMMITMENT_LABEL));
+        $self.public_coin.reseed(trace_commitments[0]);
+
+        // process auxiliary trace segments (if any), to build a set of random elements for each
+        // segment
+        for (i, commitment) in trace_commitments.iter().skip(1).enumerate() {
+            let rand_elements = $self
+                .air
+                .get_aux_trace_segment_random_elements(i, &mut $self.public_coin)
+                .map_err(|_| VerifierError::RandomCoinError)?;
+            $self.aux_trace_rand_elements.add_segment_elements(rand_elements);
+            $self.public_coin.reseed(domain_tag::<H>(TRACE_COMMITMENT_LABEL));
+            $self.public_coin.reseed(*commitment);
+        }
+
+        // build random coefficients for the composition polynomial
+        let constraint_coeffs = $self
+            .air
+            .get_constraint_composition_coefficients(&mut $self.public_coin)
+            .map_err(|_| VerifierError::RandomCoinError)?;
+        $self.constraint_coeffs = Some(constraint_coeffs);
+
+        Ok(())
+    }};
+}
+
+macro_rules! commit_constraints_shared {
+    ($self:expr, $constraint_commitment:expr) => {{
+        let constraint_commitment = $constraint_commitment;
+        $self.public_coin.reseed(domain_tag::<H>(CONSTRAINT_COMMITMENT_LABEL));
+        $self.public_coin.reseed(constraint_commitment);
+        let z = $self
+            .public_coin
+            .draw::<E>()
+            .map_err(|_| VerifierError::RandomCoinError)?;
+        $self.z = Some(z);
+        Ok(z)
+    }};
+}
+
+macro_rules! check_ood_consistency_shared {
+    ($self:expr, $ood_trace_frame:expr, $ood_constraint_evaluations:expr) => {{
+        let z = $self.z.expect("commit_constraints() must run before check_ood_consistency()");
+        let constraint_coeffs = $self
+            .constraint_coeffs
+            .clone()
+            .expect("commit_trace() must run before check_ood_consistency()");
+
+        let (ood_main_trace_frame, ood_aux_trace_frame) = $ood_trace_frame;
+        let ood_constraint_evaluation_1 = evaluate_constraints(
+            &$self.air,
+            constraint_coeffs,
+            &ood_main_trace_frame,
+            &ood_aux_trace_frame,
+            $self.aux_trace_rand_elements.clone(),
+            z,
+        );
+
+        if let Some(ref aux_trace_frame) = ood_aux_trace_frame {
+            // when the trace contains auxiliary segments, append auxiliary trace elements at the
+            // end of main trace elements for both current and next rows in the frame. this is
+            // needed to be consistent with how the prover writes OOD frame into the channel.
+            let mut current = ood_main_trace_frame.current().to_vec();
+            current.extend_from_slice(aux_trace_frame.current());
+            $self.public_coin.reseed(domain_tag::<H>(OOD_TRACE_FRAME_LABEL));
+            $self.public_coin.reseed(H::hash_elements(&current));
+
+            let mut next = ood_main_trace_frame.next().to_vec();
+            next.extend_from_slice(aux_trace_frame.next());
+            $self.public_coin.reseed(domain_tag::<H>(OOD_TRACE_FRAME_LABEL));
+            $self.public_coin.reseed(H::hash_elements(&next));
+        } else {
+            $self.public_coin.reseed(domain_tag::<H>(OOD_TRACE_FRAME_LABEL));
+            $self.public_coin.reseed(H::hash_elements(ood_main_trace_frame.current()));
+            $self.public_coin.reseed(domain_tag::<H>(OOD_TRACE_FRAME_LABEL));
+            $self.public_coin.reseed(H::hash_elements(ood_main_trace_frame.next()));
+        }
+
+        // read evaluations of composition polynomial columns sent by the prover, and reduce them
+        // into a single value by computing sum(z^i * value_i), where value_i is the evaluation of
+        // the ith column polynomial at z^m, where m is the total number of column polynomials;
+        // also, reseed the public coin with the OOD constraint evaluations received from the
+        // prover.
+        let ood_constraint_evaluations = $ood_constraint_evaluations;
+        let ood_constraint_evaluation_2 = ood_constraint_evaluations
+            .iter()
+            .enumerate()
+            .fold(E::ZERO, |result, (i, &value)| {
+                result + z.exp_vartime((i as u32).into()) * value
+            });
+        $self.public_coin.reseed(domain_tag::<H>(OOD_CONSTRAINT_EVALUATIONS_LABEL));
+        $self.public_coin.reseed(H::hash_elements(&ood_constraint_evaluations));
+
+        if ood_constraint_evaluation_1 != ood_constraint_evaluation_2 {
+            return Err(VerifierError::InconsistentOodConstraintEvaluations);
+        }
+
+        $self.ood_main_trace_frame = Some(ood_main_trace_frame);
+        $self.ood_aux_trace_frame = ood_aux_trace_frame;
+        $self.ood_constraint_evaluations = Some(ood_constraint_evaluations);
+
+        Ok(())
+    }};
+}
+
+macro_rules! draw_queries_shared {
+    ($self:expr, $pow_nonce:expr) => {{
+        let pow_nonce = $pow_nonce;
+        $self.public_coin.reseed(domain_tag::<H>(POW_NONCE_LABEL));
+        $self.public_coin.reseed_with_int(pow_nonce);
+
+        if $self.public_coin.leading_zeros() < $self.air.options().grinding_factor() {
+            return Err(VerifierError::QuerySeedProofOfWorkVerificationFailed);
+        }
+
+        let query_positions = $self
+            .public_coin
+            .draw_integers($self.air.options().num_queries(), $self.air.lde_domain_size())
+            .map_err(|_| VerifierError::RandomCoinError)?;
+        $self.query_positions = Some(query_positions.clone());
+
+        Ok(query_positions)
+    }};
+}
+
+macro_rules! verify_deep_shared {
+    ($self:expr, $query_positions:expr, $queried_trace_states:expr, $queried_constraint_evaluations:expr) => {{
+        let z = $self.z.expect("commit_constraints() must run before verify_deep()");
+        let deep_coefficients = $self
+            .deep_coefficients
+            .expect("draw_fri_layers() must run before verify_deep()");
+        let query_positions = $query_positions;
+        let ood_main_trace_frame = $self
+            .ood_main_trace_frame
+            .take()
+            .expect("check_ood_consistency() must run before verify_deep()");
+        let ood_aux_trace_frame = $self.ood_aux_trace_frame.take();
+        let ood_constraint_evaluations = $self
+            .ood_constraint_evaluations
+            .take()
+            .expect("check_ood_consistency() must run before verify_deep()");
+        let fri_verifier = $self
+            .fri_verifier
+            .take()
+            .expect("draw_fri_layers() must run before verify_deep()");
+
+        // evaluations of trace and constraint composition polynomials at the queried positions,
+        // as read by the caller; that read checked their number against the query positions.
+        let (queried_main_trace_states, queried_aux_trace_states) = $queried_trace_states;
+        let queried_constraint_evaluations = $queried_constraint_evaluations;
+
+        let composer = DeepComposer::new(&$self.air, &query_positions, z, deep_coefficients);
+        let t_composition = composer.compose_trace_columns(
+            queried_main_trace_states,
+            queried_aux_trace_states,
+            ood_main_trace_frame,
+            ood_aux_trace_frame,
+        );
+        let c_composition = composer.compose_constraint_evaluations(
+            queried_constraint_evaluations,
+            ood_constraint_evaluations,
+        );
+        let deep_evaluations = composer.combine_compositions(t_composition, c_composition);
+
+        fri_verifier
+            .verify(&mut $self.channel, &deep_evaluations, &query_positions)
+            .map_err(VerifierError::FriVerificationFailed)
+    }};
+}
+
+// STAGED VERIFIER
+// ================================================================================================
+/// Drives STARK verification one protocol transition at a time instead of running it as a single
+/// opaque call.
+///
+/// [crate::verify()] runs a [Verifier] start-to-finish and is the right entry point for ordinary,
+/// non-recursive verification. [Verifier] itself exists for recursive/aggregated setups, where a
+/// circuit needs to re-run each transition of the protocol deterministically and look at the
+/// intermediate state between steps.
+///
+/// The methods on this struct must be called in the order they're declared below; each one reads
+/// the next message(s) from `channel` and advances `public_coin`. Of the values they produce,
+/// only the OOD point `z` (from [Self::commit_constraints]) and the query positions (from
+/// [Self::draw_queries]) are returned; the constraint composition and DEEP coefficients and the
+/// FRI folding challenges are kept inside and are not exposed. Every challenge is drawn from
+/// `public_coin`; there is no way to supply one from outside. Calling the methods out of order
+/// will either panic (because a value produced by an earlier step is missing) or produce a
+/// meaningless result.
+pub struct Verifier<A, E, H>
+where
+    A: Air,
+    E: FieldElement<BaseField = A::BaseField>,
+    H: ElementHasher<BaseField = A::BaseField>,
+{
+    air: A,
+    channel: VerifierChannel<E, H>,
+    public_coin: RandomCoin<A::BaseField, H>,
+
+    aux_trace_rand_elements: AuxTraceRandElements<E>,
+    constraint_coeffs: Option<ConstraintCompositionCoefficients<E>>,
+    z: Option<E>,
+    ood_main_trace_frame: Option<EvaluationFrame<E>>,
+    ood_aux_trace_frame: Option<EvaluationFrame<E>>,
+    ood_constraint_evaluations: Option<Vec<E>>,
+    deep_coefficients: Option<DeepCompositionCoefficients<E>>,
+    fri_verifier: Option<FriVerifier<E, H>>,
+    query_positions: Option<Vec<usize>>,
+}
+
+impl<A, E, H> Verifier<A, E, H>
+where
+    A: Air,
+    E: FieldElement<BaseField = A::BaseField>,
+    H: ElementHasher<BaseField = A::BaseField>,
+{
+    /// Creates a new staged verifier around the given `air`, proof `channel`, and `public_coin`.
+    ///
+    /// This is the same entry point recursive/aggregated setups are expected to drive directly,
+    /// so it enforces the same minimum-security floor as [crate::verify()]: `air`'s options are
+    /// checked against `acceptable_options` before anything else, and no phase method below runs
+    /// if they don't meet it. Aside from that check, this performs no protocol work; call the
+    /// phase methods below in order to drive it.
+    pub fn new(
+        air: A,
+        channel: VerifierChannel<E, H>,
+        public_coin: RandomCoin<A::BaseField, H>,
+        acceptable_options: &AcceptableOptions,
+    ) -> Result<Self, VerifierError> {
+        security::enforce_acceptable_options::<A, H>(&air, acceptable_options)?;
+
+        Ok(Verifier {
+            air,
+            channel,
+            public_coin,
+            aux_trace_rand_elements: AuxTraceRandElements::<E>::new(),
+            constraint_coeffs: None,
+            z: None,
+            ood_main_trace_frame: None,
+            ood_aux_trace_frame: None,
+            ood_constraint_evaluations: None,
+            deep_coefficients: None,
+            fri_verifier: None,
+            query_positions: None,
+        })
+    }
+
+    // PHASE 1: TRACE COMMITMENT
+    // --------------------------------------------------------------------------------------------
+    /// Absorbs the commitments to the main and (if any) auxiliary trace segments, drawing the
+    /// random elements needed for each auxiliary segment and the coefficients used to compute the
+    /// constraint composition polynomial.
+    pub fn commit_trace(&mut self) -> Result<(), VerifierError> {
+        commit_trace_shared!(self, self.channel.read_trace_commitments())
+    }
+
+    // PHASE 2: CONSTRAINT COMMITMENT
+    // --------------------------------------------------------------------------------------------
+    /// Absorbs the commitment to the constraint composition polynomial and draws the
+    /// out-of-domain point `z` at which the prover will evaluate it.
+    pub fn commit_constraints(&mut self) -> Result<E, VerifierError> {
+        commit_constraints_shared!(self, self.channel.read_constraint_commitment())
+    }
+
+    // PHASE 3: OOD CONSISTENCY CHECK
+    // --------------------------------------------------------------------------------------------
+    /// Makes sure that evaluating the constraints over the out-of-domain trace frame sent by the
+    /// prover produces the same result as reducing the out-of-domain constraint composition
+    /// evaluations also sent by the prover, and absorbs both into the transcript.
+    pub fn check_ood_consistency(&mut self) -> Result<(), VerifierError> {
+        check_ood_consistency_shared!(
+            self,
+            self.channel.read_ood_trace_frame(),
+            self.channel.read_ood_constraint_evaluations()
+        )
+    }
+
+    // PHASE 4: FRI COMMITMENTS
+    // --------------------------------------------------------------------------------------------
+    /// Draws the coefficients used to compute the DEEP composition polynomial, then absorbs the
+    /// FRI layer commitments and draws the folding challenge (alpha) for each layer.
+    ///
+    /// `FriVerifier::new` performs its own channel reads synchronously against the channel
+    /// reference it is given, so unlike the other phases this one has no async counterpart - it
+    /// is called unchanged from the async driver too.
+    pub fn draw_fri_layers(&mut self) -> Result<(), VerifierError> {
+        let deep_coefficients = self
+            .air
+            .get_deep_composition_coefficients::<E, H>(&mut self.public_coin)
+            .map_err(|_| VerifierError::RandomCoinError)?;
+        self.deep_coefficients = Some(deep_coefficients);
+
+        // mark the start of the FRI commit phase distinctly in the transcript; the per-layer
+        // reseeds that follow (one per FRI folding round) are performed inside `FriVerifier::new`
+        // itself.
+        self.public_coin.reseed(domain_tag::<H>(FRI_LAYER_LABEL));
+
+        let fri_verifier = FriVerifier::new(
+            &mut self.channel,
+            &mut self.public_coin,
+            self.air.options().to_fri_options(),
+            self.air.trace_poly_degree(),
+        )
+        .map_err(VerifierError::FriVerificationFailed)?;
+        self.fri_verifier = Some(fri_verifier);
+
+        Ok(())
+    }
+
+    // PHASE 5: QUERIES
+    // --------------------------------------------------------------------------------------------
+    /// Absorbs the proof-of-work nonce, checks the grinding requirement, and draws the
+    /// pseudo-random LDE-domain query positions the prover must open decommitments against.
+    ///
+    /// Note: `draw_integers` can draw the same LDE-domain position more than once, and the
+    /// positions returned here are not deduplicated before being used for the Merkle
+    /// decommitments read in [Self::verify_deep]. Collapsing them to a unique, sorted set (to
+    /// avoid re-verifying and re-transmitting the same decommitment under repeated positions)
+    /// needs a `num_unique_queries` field on `StarkProof` and a matching change to how the prover
+    /// writes opened rows - both on the `air`/prover side, which this verifier-only tree has no
+    /// copy of, so it cannot be implemented here without inventing that wire format from nothing.
+    pub fn draw_queries(&mut self) -> Result<Vec<usize>, VerifierError> {
+        draw_queries_shared!(self, self.channel.read_pow_nonce())
+    }
+
+    // PHASE 6 & 7: DEEP COMPOSITION AND LOW-DEGREE VERIFICATION
+    // --------------------------------------------------------------------------------------------
+    /// Reads the queried trace and constraint evaluations, composes them into the DEEP
+    /// composition polynomial, and runs the FRI low-degree check against it.
+    pub fn verify_deep(mut self) -> Result<(), VerifierError> {
+        let query_positions = self
+            .query_positions
+            .clone()
+            .expect("draw_queries() must run before verify_deep()");
+        let queried_trace_states = self.channel.read_queried_trace_states(&query_positions)?;
+        let queried_constraint_evaluations =
+            self.channel.read_constraint_evaluations(&query_positions)?;
+        verify_deep_shared!(self, query_positions, queried_trace_states, queried_constraint_evaluations)
+    }
+}
+
+// ASYNC PHASES
+// ================================================================================================
+/// Async counterparts of the phases above, letting `async_verify.rs` drive this same state
+/// machine from an async executor without blocking it (see its module docs for why this is not
+/// incremental byte-source parsing). Each one just supplies an `.await`ed read to the same
+/// `*_shared!` macro its synchronous counterpart uses above, so the protocol logic itself - reseed
+/// order, domain-separation labels, error mapping - is defined exactly once.
+#[cfg(feature = "async")]
+impl<A, E, H> Verifier<A, E, H>
+where
+    A: Air,
+    E: FieldElement<BaseField = A::BaseField>,
+    H: ElementHasher<BaseField = A::BaseField>,
+{
+    /// Async counterpart of [Self::commit_trace].
+    pub async fn commit_trace_async(&mut self) -> Result<(), VerifierError> {
+        commit_trace_shared!(self, self.channel.read_trace_commitments_async().await)
+    }
+
+    /// Async counterpart of [Self::commit_constraints].
+    pub async fn commit_constraints_async(&mut self) -> Result<E, VerifierError> {
+        commit_constraints_shared!(self, self.channel.read_constraint_commitment_async().await)
+    }
+
+    /// Async counterpart of [Self::check_ood_consistency].
+    pub async fn check_ood_consistency_async(&mut self) -> Result<(), VerifierError> {
+        check_ood_consistency_shared!(
+            self,
+            self.channel.read_ood_trace_frame_async().await,
+            self.channel.read_ood_constraint_evaluations_async().await
+        )
+    }
+
+    /// Async counterpart of [Self::draw_queries].
+    pub async fn draw_queries_async(&mut self) -> Result<Vec<usize>, VerifierError> {
+        draw_queries_shared!(self, self.channel.read_pow_nonce_async().await)
+    }
+
+    /// Async counterpart of [Self::verify_deep].
+    pub async fn verify_deep_async(mut self) -> Result<(), VerifierError> {
+        let query_positions = self
+            .query_positions
+            .clone()
+            .expect("draw_queries_async() must run before verify_deep_async()");
+        let queried_trace_states = self
+            .channel
+            .read_queried_trace_states_async(&query_positions)
+            .await?;
+        let queried_constraint_evaluations = self
+            .channel
+            .read_constraint_evaluations_async(&query_positions)
+            .await?;
+        verify_deep_shared!(self, query_positions, queried_trace_states, queried_constraint_evaluations)
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use crypto::ElementHasher;
+
+// PROTOCOL IDENTITY
+// ================================================================================================
+/// A fixed tag identifying this protocol and its transcript revision. Mixed into the very first
+/// bytes of the public coin seed so that a proof generated under a different protocol or
+/// transcript revision can never be replayed as one of ours.
+pub(crate) const PROTOCOL_TAG: &[u8] = b"winterfell-stark-v1";
+
+// DOMAIN SEPARATION LABELS
+// ================================================================================================
+/// Distinct labels mixed into the public coin immediately before each reseed of the Fiat-Shamir
+/// transcript. Without these, two reseed points that happen to absorb equal-looking data (e.g. an
+/// OOD trace frame and an OOD constraint evaluation of the same hash) would derive identical
+/// challenges from that point on; the labels make every reseed event unambiguous, regardless of
+/// what is being committed to. This covers the reseeds the verifier performs itself: the
+/// per-layer reseeds done inside `FriVerifier::new` get no label of their own, and
+/// [FRI_LAYER_LABEL] is mixed in once, before the whole FRI commit phase.
+pub(crate) const TRACE_COMMITMENT_LABEL: u64 = 1;
+pub(crate) const CONSTRAINT_COMMITMENT_LABEL: u64 = 2;
+pub(crate) const OOD_TRACE_FRAME_LABEL: u64 = 3;
+pub(crate) const OOD_CONSTRAINT_EVALUATIONS_LABEL: u64 = 4;
+pub(crate) const FRI_LAYER_LABEL: u64 = 5;
+pub(crate) const POW_NONCE_LABEL: u64 = 6;
+
+/// Derives a digest unique to `label`, suitable for reseeding a [crypto::RandomCoin] with before
+/// absorbing the actual prover message for that transcript step.
+pub(crate) fn domain_tag<H: ElementHasher>(label: u64) -> H::Digest {
+    H::merge_with_int(H::Digest::default(), label)
+}